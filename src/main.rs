@@ -1,8 +1,10 @@
-use std::cmp::max;
+use flate2::read::GzDecoder;
 use std::collections::BTreeMap;
 use std::env;
 use std::fs::File;
-use std::io::{self, Read};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use thiserror::Error;
 
 struct Image<T> {
     width: u32,
@@ -31,110 +33,318 @@ impl<T> Image<T> {
             print!("\x1b[0m\n");
         }
     }
+
+    // Write this image to `path` as an 8-bit grayscale PNG, using
+    // `convert` to map each element to a gray value.
+    fn to_png<F: Fn(&T) -> u8>(&self, path: &Path, convert: F) -> std::io::Result<()> {
+        let gray: Vec<u8> = self.data.iter().map(convert).collect();
+        let buffer = image::GrayImage::from_raw(self.width, self.height, gray).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "image buffer size does not match width * height",
+            )
+        })?;
+        buffer.save(path).map_err(|e| io::Error::other(e.to_string()))
+    }
 }
 
 type MnistImage = Image<u8>;
 
-fn maybe_report_magic_mismatch(filename: &str, actual: u32, expected: u32) -> std::io::Result<()> {
-    if expected != actual {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!(
-                "{}: Unexpected magic number; Got {:#08x}; expected {:#08x}",
-                filename, actual, expected
-            ),
-        ));
+// The element type encoded in the third byte of an IDX magic number. The
+// numeric values are the ones defined by the IDX file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdxType {
+    U8,
+    I8,
+    I16,
+    I32,
+    F32,
+    F64,
+}
+
+impl IdxType {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x08 => Some(IdxType::U8),
+            0x09 => Some(IdxType::I8),
+            0x0B => Some(IdxType::I16),
+            0x0C => Some(IdxType::I32),
+            0x0D => Some(IdxType::F32),
+            0x0E => Some(IdxType::F64),
+            _ => None,
+        }
+    }
+
+    // Width in bytes of a single element, as stored big-endian.
+    fn element_size(self) -> usize {
+        match self {
+            IdxType::U8 | IdxType::I8 => 1,
+            IdxType::I16 => 2,
+            IdxType::I32 | IdxType::F32 => 4,
+            IdxType::F64 => 8,
+        }
     }
-    Ok(())
 }
 
-fn maybe_report_unexpected_filesize(
-    filename: &str,
-    file: &File,
-    expected_size: usize,
-) -> std::io::Result<()> {
-    let actual_filesize = file.metadata()?.len() as usize;
-    if actual_filesize != expected_size {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!(
-                "{}: Unexpected file size; expected {}, got {}",
-                filename, expected_size, actual_filesize
-            ),
-        ));
+// A Rust type that an IDX element can be decoded into at its own native
+// width, with no lossy narrowing. Implemented for every type `IdxType`
+// names, so `read_idx_images::<T>` can instantiate `Image<T>` (e.g.
+// `Image<f32>` for a 0x0D file) instead of forcing every element through
+// a shared byte-range type.
+trait IdxElement: Sized {
+    const IDX_TYPE: IdxType;
+    fn decode_be(bytes: &[u8]) -> Self;
+}
+
+impl IdxElement for u8 {
+    const IDX_TYPE: IdxType = IdxType::U8;
+    fn decode_be(bytes: &[u8]) -> Self {
+        bytes[0]
     }
-    Ok(())
 }
 
-fn read_be_u32(file: &mut File) -> std::io::Result<u32> {
-    let mut buffer = vec![0; 4];
-    file.read_exact(&mut buffer)?;
-    Ok(u32::from_be_bytes(buffer[0..4].try_into().unwrap()))
+impl IdxElement for i8 {
+    const IDX_TYPE: IdxType = IdxType::I8;
+    fn decode_be(bytes: &[u8]) -> Self {
+        bytes[0] as i8
+    }
 }
 
-fn read_labels(filename: &str) -> std::io::Result<Vec<u8>> {
-    const LABEL_MAGIC_NUMBER: u32 = 0x801;
-    let mut file = File::open(filename)?;
-    let magic = read_be_u32(&mut file)?;
-    maybe_report_magic_mismatch(filename, magic, LABEL_MAGIC_NUMBER)?;
-    let count = read_be_u32(&mut file)? as usize;
-    let expected_filesize = 8 + count;
-    maybe_report_unexpected_filesize(filename, &file, expected_filesize)?;
+impl IdxElement for i16 {
+    const IDX_TYPE: IdxType = IdxType::I16;
+    fn decode_be(bytes: &[u8]) -> Self {
+        i16::from_be_bytes(bytes.try_into().unwrap())
+    }
+}
 
-    let mut result = vec![0; count];
-    file.read_exact(&mut result)?;
-    return Ok(result);
+impl IdxElement for i32 {
+    const IDX_TYPE: IdxType = IdxType::I32;
+    fn decode_be(bytes: &[u8]) -> Self {
+        i32::from_be_bytes(bytes.try_into().unwrap())
+    }
+}
+
+impl IdxElement for f32 {
+    const IDX_TYPE: IdxType = IdxType::F32;
+    fn decode_be(bytes: &[u8]) -> Self {
+        f32::from_be_bytes(bytes.try_into().unwrap())
+    }
+}
+
+impl IdxElement for f64 {
+    const IDX_TYPE: IdxType = IdxType::F64;
+    fn decode_be(bytes: &[u8]) -> Self {
+        f64::from_be_bytes(bytes.try_into().unwrap())
+    }
+}
+
+// The parsed IDX header: the element type from the magic number's third
+// byte, and the dimension sizes declared by its fourth byte and the
+// `u32` dimensions that follow (e.g. `[count]` for labels, `[count,
+// rows, columns]` for images).
+struct IdxHeader {
+    element_type: IdxType,
+    dimensions: Vec<u32>,
+}
+
+// Errors this crate can produce while loading and evaluating IDX data.
+// Carrying the offending filename in each per-file variant (and, for
+// shape errors, the declared and expected dimension counts) lets callers
+// match on and report the exact failure instead of parsing an opaque
+// string.
+#[derive(Debug, Error)]
+enum MnistError {
+    #[error("{file}: unexpected magic number; got {got:#08x}, expected the first two bytes to be zero")]
+    BadMagic { file: String, got: u32 },
+
+    #[error("{file}: unknown IDX element type {code:#04x}")]
+    UnknownElementType { file: String, code: u8 },
+
+    #[error(
+        "{file}: IDX element type {element_type:?} is not supported here; this tool classifies u8 pixel data"
+    )]
+    UnsupportedElementType { file: String, element_type: IdxType },
+
+    #[error(
+        "{file}: unexpected IDX shape; got {got_dimensions} dimension(s), expected {expected_dimensions}"
+    )]
+    DimensionMismatch {
+        file: String,
+        got_dimensions: usize,
+        expected_dimensions: usize,
+    },
+
+    #[error("{file}: unexpected trailing data; {extra} extra byte(s) after declared contents")]
+    SizeMismatch { file: String, extra: usize },
+
+    #[error("labels vs. image count mismatch: {labels} labels, {images} images")]
+    LabelImageCountMismatch { labels: usize, images: usize },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+fn read_idx_header(filename: &str, reader: &mut impl Read) -> Result<IdxHeader, MnistError> {
+    let mut prefix = [0; 4];
+    reader.read_exact(&mut prefix)?;
+    if prefix[0] != 0 || prefix[1] != 0 {
+        return Err(MnistError::BadMagic {
+            file: filename.to_string(),
+            got: u32::from_be_bytes(prefix),
+        });
+    }
+    let element_type = IdxType::from_code(prefix[2]).ok_or_else(|| MnistError::UnknownElementType {
+        file: filename.to_string(),
+        code: prefix[2],
+    })?;
+    let dimension_count = prefix[3] as usize;
+    let mut dimensions = Vec::with_capacity(dimension_count);
+    for _ in 0..dimension_count {
+        dimensions.push(read_be_u32(reader)?);
+    }
+    Ok(IdxHeader {
+        element_type,
+        dimensions,
+    })
 }
 
-fn read_images(filename: &str) -> std::io::Result<Vec<MnistImage>> {
-    const IMAGE_MAGIC_NUMBER: u32 = 0x803;
-    let mut file = File::open(filename)?;
-    let magic = read_be_u32(&mut file)?;
-    maybe_report_magic_mismatch(filename, magic, IMAGE_MAGIC_NUMBER)?;
-    let count = read_be_u32(&mut file)? as usize;
-    let rows = read_be_u32(&mut file)?;
-    let columns = read_be_u32(&mut file)?;
-    let expected_filesize = 16 + count * (rows * columns) as usize;
-    maybe_report_unexpected_filesize(filename, &file, expected_filesize)?;
+fn maybe_report_unexpected_dimensions(
+    filename: &str,
+    header: &IdxHeader,
+    expected_dimensions: usize,
+) -> Result<(), MnistError> {
+    if header.dimensions.len() != expected_dimensions {
+        return Err(MnistError::DimensionMismatch {
+            file: filename.to_string(),
+            got_dimensions: header.dimensions.len(),
+            expected_dimensions,
+        });
+    }
+    Ok(())
+}
 
+// Read `count` elements of `T` (e.g. `u8`, `f32`, ...) at their native
+// big-endian width. No narrowing happens here: the caller picks `T` to
+// match the file's declared `IdxType`, so e.g. a 0x0D (f32) file is read
+// back as `f32` values, not squashed into another type's range.
+fn read_idx_elements<T: IdxElement>(
+    reader: &mut impl Read,
+    count: usize,
+) -> Result<Vec<T>, MnistError> {
+    let mut buffer = vec![0; T::IDX_TYPE.element_size()];
+    let mut result = Vec::with_capacity(count);
+    for _ in 0..count {
+        reader.read_exact(&mut buffer)?;
+        result.push(T::decode_be(&buffer));
+    }
+    Ok(result)
+}
+
+// Read `header.dimensions[0]` images of `header.dimensions[1] x
+// header.dimensions[2]` pixels of type `T`, matching the file's declared
+// element type.
+fn read_idx_images<T: IdxElement>(
+    header: &IdxHeader,
+    reader: &mut impl Read,
+) -> Result<Vec<Image<T>>, MnistError> {
+    let count = header.dimensions[0] as usize;
+    let rows = header.dimensions[1];
+    let columns = header.dimensions[2];
     let image_size = (columns * rows) as usize;
-    let mut result: Vec<MnistImage> = Vec::new();
+
+    let mut result = Vec::with_capacity(count);
     for _ in 0..count {
-        let mut data = vec![0; image_size];
-        file.read_exact(&mut data)?;
-        result.push(MnistImage::new(columns, rows, data));
+        let data = read_idx_elements::<T>(reader, image_size)?;
+        result.push(Image::new(columns, rows, data));
     }
-    return Ok(result);
+    Ok(result)
 }
 
-fn usage() -> std::io::Result<()> {
-    println!("Usage: handwriting-detect-rs <labels-file> <image-file>\n");
-    return Err(io::Error::new(
-        io::ErrorKind::InvalidInput,
-        "expected arguments",
-    ));
+fn maybe_report_unsupported_element_type(
+    filename: &str,
+    header: &IdxHeader,
+    expected: IdxType,
+) -> Result<(), MnistError> {
+    if header.element_type != expected {
+        return Err(MnistError::UnsupportedElementType {
+            file: filename.to_string(),
+            element_type: header.element_type,
+        });
+    }
+    Ok(())
 }
 
-fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        return usage();
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Open `filename` for reading, transparently unwrapping a gzip stream if
+// the name ends in `.gz` or the leading bytes are the gzip magic.
+fn open_reader(filename: &str) -> std::io::Result<Box<dyn Read>> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    let is_gzip = filename.ends_with(".gz") || reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    if is_gzip {
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
     }
-    let labels = read_labels(&args[1])?;
-    let images = read_images(&args[2])?;
+}
 
-    println!("Getting {} labels, {} images", labels.len(), images.len());
-    if labels.len() != images.len() {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "labels vs. image mismatch",
-        ));
+// Since the decompressed length of a gzipped stream isn't known upfront,
+// we check the size after the fact: there must be no bytes left once the
+// header-declared contents have been read.
+fn maybe_report_unexpected_trailing_data(
+    filename: &str,
+    reader: &mut impl Read,
+) -> Result<(), MnistError> {
+    let mut remainder = Vec::new();
+    let extra = reader.read_to_end(&mut remainder)?;
+    if extra != 0 {
+        return Err(MnistError::SizeMismatch {
+            file: filename.to_string(),
+            extra,
+        });
     }
+    Ok(())
+}
+
+fn read_be_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_be_bytes(buffer))
+}
+
+fn read_labels(filename: &str) -> Result<Vec<u8>, MnistError> {
+    let mut reader = open_reader(filename)?;
+    let header = read_idx_header(filename, &mut reader)?;
+    maybe_report_unexpected_dimensions(filename, &header, 1)?;
+    maybe_report_unsupported_element_type(filename, &header, IdxType::U8)?;
+    let count = header.dimensions[0] as usize;
+
+    let result = read_idx_elements::<u8>(&mut reader, count)?;
+    maybe_report_unexpected_trailing_data(filename, &mut reader)?;
+    return Ok(result);
+}
+
+fn read_images(filename: &str) -> Result<Vec<MnistImage>, MnistError> {
+    let mut reader = open_reader(filename)?;
+    let header = read_idx_header(filename, &mut reader)?;
+    maybe_report_unexpected_dimensions(filename, &header, 3)?;
+    maybe_report_unsupported_element_type(filename, &header, IdxType::U8)?;
+
+    let result = read_idx_images::<u8>(&header, &mut reader)?;
+    maybe_report_unexpected_trailing_data(filename, &mut reader)?;
+    return Ok(result);
+}
+
+// A per-label mean image, used as the centroid for nearest-centroid
+// classification below.
+type CentroidImage = Image<f32>;
 
-    // Sum up all the images for the corresponding labels to get an 'average'
-    // image.
+// Sum up all the images for the corresponding labels, then divide each
+// sum by its image count to get a per-label 'average' (centroid) image.
+fn compute_centroids(labels: &[u8], images: &[MnistImage]) -> BTreeMap<u8, CentroidImage> {
     type SumImage = Image<u32>;
     let mut label2sum: BTreeMap<u8, SumImage> = BTreeMap::new();
+    let mut label2count: BTreeMap<u8, u32> = BTreeMap::new();
     for i in 0..labels.len() {
         let label = &labels[i];
         let image = &images[i];
@@ -149,17 +359,309 @@ fn main() -> std::io::Result<()> {
                 s.data[pixel] += image.data[pixel] as u32;
             }
         }
+        *label2count.entry(*label).or_insert(0) += 1;
+    }
+
+    label2sum
+        .into_iter()
+        .filter_map(|(label, sum)| {
+            let count = *label2count.get(&label).unwrap_or(&0);
+            if count == 0 {
+                return None;
+            }
+            let data = sum
+                .data
+                .iter()
+                .map(|&total| total as f32 / count as f32)
+                .collect();
+            Some((label, CentroidImage::new(sum.width, sum.height, data)))
+        })
+        .collect()
+}
+
+// Classify an image as the label of its nearest centroid, using squared
+// Euclidean distance over the pixels. Centroids whose dimensions don't
+// match the image are skipped rather than compared.
+fn classify(image: &MnistImage, centroids: &BTreeMap<u8, CentroidImage>) -> Option<u8> {
+    let mut best: Option<(u8, f32)> = None;
+    for (label, centroid) in centroids {
+        if centroid.width != image.width || centroid.height != image.height {
+            continue;
+        }
+        let distance: f32 = image
+            .data
+            .iter()
+            .zip(centroid.data.iter())
+            .map(|(pixel, mean)| {
+                let diff = *pixel as f32 - mean;
+                diff * diff
+            })
+            .sum();
+        if best.is_none_or(|(_, best_distance)| distance < best_distance) {
+            best = Some((*label, distance));
+        }
+    }
+    best.map(|(label, _)| label)
+}
+
+const NUM_DIGIT_CLASSES: usize = 10;
+
+// Classify every test image against the given centroids, report overall
+// accuracy plus per-class precision/recall, and optionally render the
+// 10x10 confusion matrix (`counts[true_label][predicted_label]`) to the
+// terminal with the same ANSI-block shading used for images.
+fn evaluate(
+    centroids: &BTreeMap<u8, CentroidImage>,
+    labels: &[u8],
+    images: &[MnistImage],
+    render_matrix: bool,
+) {
+    let mut confusion = [[0u32; NUM_DIGIT_CLASSES]; NUM_DIGIT_CLASSES];
+    let mut correct: u32 = 0;
+    // Only digits 0-9 are counted; a sample with an out-of-range true or
+    // predicted label is excluded from the matrix *and* the accuracy
+    // denominator, rather than being silently counted as "total" but
+    // never "correct".
+    let mut evaluated: u32 = 0;
+    for (label, image) in labels.iter().zip(images.iter()) {
+        let Some(predicted) = classify(image, centroids) else {
+            continue;
+        };
+        let (true_label, predicted_label) = (*label as usize, predicted as usize);
+        if true_label >= NUM_DIGIT_CLASSES || predicted_label >= NUM_DIGIT_CLASSES {
+            continue;
+        }
+        evaluated += 1;
+        confusion[true_label][predicted_label] += 1;
+        if predicted == *label {
+            correct += 1;
+        }
+    }
+
+    println!(
+        "Overall accuracy: {}/{} ({:.2}%)",
+        correct,
+        evaluated,
+        100.0 * correct as f32 / evaluated as f32
+    );
+    for true_label in 0..NUM_DIGIT_CLASSES {
+        let true_positive = confusion[true_label][true_label];
+        let row_total: u32 = confusion[true_label].iter().sum();
+        let column_total: u32 = confusion.iter().map(|row| row[true_label]).sum();
+        let precision = if column_total == 0 {
+            0.0
+        } else {
+            true_positive as f32 / column_total as f32
+        };
+        let recall = if row_total == 0 {
+            0.0
+        } else {
+            true_positive as f32 / row_total as f32
+        };
+        println!(
+            "Label {}: {}/{} correct, precision {:.2}%, recall {:.2}%",
+            true_label,
+            true_positive,
+            row_total,
+            100.0 * precision,
+            100.0 * recall
+        );
+    }
+
+    if render_matrix {
+        println!("Confusion matrix (rows: true label, columns: predicted):");
+        let data: Vec<u32> = confusion.iter().flatten().copied().collect();
+        let matrix_image = Image::new(NUM_DIGIT_CLASSES as u32, NUM_DIGIT_CLASSES as u32, data);
+        let max_value = *matrix_image.data.iter().max().unwrap_or(&0).max(&1);
+        matrix_image.print_with_conversion(|value| (255 * value / max_value) as u8);
+    }
+}
+
+fn usage() -> Result<(), MnistError> {
+    println!(
+        "Usage: handwriting-detect-rs [--png <outdir>] [--png-samples] [--confusion] <labels-file> <image-file> [<test-labels-file> <test-image-file>]\n"
+    );
+    Err(io::Error::new(io::ErrorKind::InvalidInput, "expected arguments").into())
+}
+
+fn main() -> Result<(), MnistError> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut positional: Vec<String> = Vec::new();
+    let mut png_dir: Option<String> = None;
+    let mut png_samples = false;
+    let mut render_matrix = false;
+    let mut i = 1;
+    while i < args.len() {
+        if args[i] == "--png" {
+            i += 1;
+            if i >= args.len() {
+                return usage();
+            }
+            png_dir = Some(args[i].clone());
+        } else if args[i] == "--png-samples" {
+            png_samples = true;
+        } else if args[i] == "--confusion" {
+            render_matrix = true;
+        } else {
+            positional.push(args[i].clone());
+        }
+        i += 1;
+    }
+    if positional.len() != 2 && positional.len() != 4 {
+        return usage();
+    }
+    if png_samples && png_dir.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--png-samples requires --png <outdir>",
+        )
+        .into());
+    }
+
+    let labels = read_labels(&positional[0])?;
+    let images = read_images(&positional[1])?;
+
+    println!("Getting {} labels, {} images", labels.len(), images.len());
+    if labels.len() != images.len() {
+        return Err(MnistError::LabelImageCountMismatch {
+            labels: labels.len(),
+            images: images.len(),
+        });
     }
 
-    for (label, image) in &label2sum {
+    let centroids = compute_centroids(&labels, &images);
+
+    if let Some(dir) = &png_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    for (label, image) in &centroids {
         println!("Label: {} ------------------------------\n", label);
-        let mut max_value: u32 = 0;
+        let mut max_value: f32 = 0.0;
         for val in image.data.iter() {
-	    max_value = max(max_value, *val);
-	}
-        image.print_with_conversion(|value| (255 * value / max_value) as u8);
+            max_value = max_value.max(*val);
+        }
+        let convert = |value: &f32| (255.0 * value / max_value) as u8;
+        image.print_with_conversion(convert);
+        if let Some(dir) = &png_dir {
+            let path = Path::new(dir).join(format!("label_{}.png", label));
+            image.to_png(&path, convert)?;
+        }
     }
 
+    if png_samples {
+        // Unwrap is safe: checked against png_dir.is_none() above.
+        let samples_dir = Path::new(png_dir.as_ref().unwrap()).join("samples");
+        std::fs::create_dir_all(&samples_dir)?;
+        for (index, (label, image)) in labels.iter().zip(images.iter()).enumerate() {
+            let path = samples_dir.join(format!("{}_{}.png", label, index));
+            image.to_png(&path, |value| *value)?;
+        }
+    }
+
+    if positional.len() == 4 {
+        let test_labels = read_labels(&positional[2])?;
+        let test_images = read_images(&positional[3])?;
+        println!(
+            "Getting {} test labels, {} test images",
+            test_labels.len(),
+            test_images.len()
+        );
+        if test_labels.len() != test_images.len() {
+            return Err(MnistError::LabelImageCountMismatch {
+                labels: test_labels.len(),
+                images: test_images.len(),
+            });
+        }
+        evaluate(&centroids, &test_labels, &test_images, render_matrix);
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_picks_the_nearest_centroid() {
+        let labels = vec![0, 0, 1, 1];
+        let images = vec![
+            MnistImage::new(2, 2, vec![0, 0, 0, 0]),
+            MnistImage::new(2, 2, vec![10, 10, 10, 10]),
+            MnistImage::new(2, 2, vec![200, 200, 200, 200]),
+            MnistImage::new(2, 2, vec![220, 220, 220, 220]),
+        ];
+        let centroids = compute_centroids(&labels, &images);
+        assert_eq!(centroids.len(), 2);
+
+        let near_zero = MnistImage::new(2, 2, vec![5, 5, 5, 5]);
+        assert_eq!(classify(&near_zero, &centroids), Some(0));
+
+        let near_one = MnistImage::new(2, 2, vec![210, 210, 210, 210]);
+        assert_eq!(classify(&near_one, &centroids), Some(1));
+    }
+
+    #[test]
+    fn classify_skips_centroids_with_mismatched_dimensions() {
+        let labels = vec![0];
+        let images = vec![MnistImage::new(2, 2, vec![0, 0, 0, 0])];
+        let centroids = compute_centroids(&labels, &images);
+
+        let wrong_shape = MnistImage::new(1, 1, vec![0]);
+        assert_eq!(classify(&wrong_shape, &centroids), None);
+    }
+
+    #[test]
+    fn decode_be_u8() {
+        assert_eq!(u8::decode_be(&[0x7f]), 0x7f);
+    }
+
+    #[test]
+    fn decode_be_i8() {
+        assert_eq!(i8::decode_be(&[0xff]), -1);
+    }
+
+    #[test]
+    fn decode_be_i16() {
+        assert_eq!(i16::decode_be(&[0xff, 0x38]), -200);
+    }
+
+    #[test]
+    fn decode_be_i32() {
+        assert_eq!(i32::decode_be(&[0xff, 0xff, 0xff, 0x38]), -200);
+    }
+
+    #[test]
+    fn decode_be_f32() {
+        assert_eq!(f32::decode_be(&0.5f32.to_be_bytes()), 0.5);
+    }
+
+    #[test]
+    fn decode_be_f64() {
+        assert_eq!(f64::decode_be(&0.5f64.to_be_bytes()), 0.5);
+    }
+
+    #[test]
+    fn read_idx_elements_round_trips_u8() {
+        let bytes = [1u8, 2, 3, 4];
+        let mut cursor = io::Cursor::new(bytes);
+        let values = read_idx_elements::<u8>(&mut cursor, 4).unwrap();
+        assert_eq!(values, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn read_idx_elements_round_trips_f32_without_clamping() {
+        // Regression test: fractional normalized pixel values in
+        // `0.0..=1.0` must survive a round trip as f32, not get
+        // force-narrowed to u8 and truncated to near-black.
+        let values = [0.1f32, 0.5, 0.9, 1.0];
+        let mut bytes = Vec::new();
+        for value in values {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        let mut cursor = io::Cursor::new(bytes);
+        let decoded = read_idx_elements::<f32>(&mut cursor, values.len()).unwrap();
+        assert_eq!(decoded, values);
+    }
+}